@@ -0,0 +1,170 @@
+//! Per-entry HTTP cache semantics on top of moka.
+//!
+//! Unlike a single global TTL, each entry's freshness window comes from the
+//! origin's own `Cache-Control: max-age` (see [`parse_cache_control`]), and we
+//! keep enough of the origin's validators (`ETag` / `Last-Modified`) around to
+//! revalidate with a conditional GET once that window lapses instead of
+//! blindly refetching the whole body.
+
+use std::time::{Duration, Instant};
+
+use moka::Expiry;
+use moka::future::Cache;
+
+use crate::MetaData;
+use crate::disk::DiskCache;
+
+/// Upper bound on the TTL we'll honor from an origin, regardless of how long
+/// its `max-age` asks for.
+const MAX_TTL: Duration = Duration::from_secs(7 * 86400); // 1 week
+/// TTL applied when the origin sends no `Cache-Control` (or an unparsable
+/// one), and for fetch failures.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(86400); // 1 day
+
+/// What a `Cache-Control` response header tells us to do with an entry.
+pub enum CacheControl {
+    /// `no-store`: don't cache the response at all.
+    NoStore,
+    /// Cache for the given duration (already clamped to [`MAX_TTL`]).
+    Ttl(Duration),
+}
+
+/// Parses the `max-age` / `no-store` / `no-cache` directives out of a
+/// `Cache-Control` header value. `no-cache` is treated as a zero-length TTL
+/// so the entry is stored but revalidated on every access, matching its HTTP
+/// semantics without a separate code path.
+pub fn parse_cache_control(header: Option<&str>) -> CacheControl {
+    let Some(header) = header else {
+        return CacheControl::Ttl(DEFAULT_TTL);
+    };
+    let (mut no_store, mut no_cache, mut max_age) = (false, false, None);
+    for directive in header.split(',') {
+        let directive = directive.trim();
+        if directive.eq_ignore_ascii_case("no-store") {
+            no_store = true;
+        } else if directive.eq_ignore_ascii_case("no-cache") {
+            no_cache = true;
+        } else if let Some(value) = directive
+            .to_ascii_lowercase()
+            .strip_prefix("max-age=")
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            max_age = Some(value);
+        }
+    }
+    if no_store {
+        return CacheControl::NoStore;
+    }
+    if no_cache {
+        return CacheControl::Ttl(Duration::ZERO);
+    }
+    match max_age {
+        Some(seconds) => CacheControl::Ttl(Duration::from_secs(seconds).min(MAX_TTL)),
+        None => CacheControl::Ttl(DEFAULT_TTL),
+    }
+}
+
+/// A cached `link_preview` result plus what we need to revalidate it.
+#[derive(Clone)]
+pub struct CacheEntry {
+    pub result: Result<MetaData, String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    /// How long this entry is considered fresh from the moment it was stored.
+    pub ttl: Duration,
+    /// When `ttl` lapses and the entry needs revalidation.
+    pub valid_until: Instant,
+}
+
+impl CacheEntry {
+    pub fn new(
+        result: Result<MetaData, String>,
+        etag: Option<String>,
+        last_modified: Option<String>,
+        ttl: Duration,
+    ) -> Self {
+        CacheEntry { result, etag, last_modified, ttl, valid_until: Instant::now() + ttl }
+    }
+
+    pub fn is_fresh(&self) -> bool {
+        Instant::now() < self.valid_until
+    }
+}
+
+/// Keeps entries around for twice their TTL from the moment they're stored
+/// (one more TTL past the point they go stale, and never less than
+/// [`DEFAULT_TTL`]), so a stale-but-still-present entry can be revalidated with a conditional GET
+/// instead of moka evicting it out from under us before we get the chance.
+/// `no-cache` entries have a zero TTL by design (see [`parse_cache_control`])
+/// but still need a real grace window — without a floor they'd be evicted
+/// the instant they're stored, and their `ETag`/`Last-Modified` would never
+/// survive to be revalidated.
+struct StaleGracePeriod;
+
+impl StaleGracePeriod {
+    fn grace(entry: &CacheEntry) -> Duration {
+        (entry.ttl * 2).max(DEFAULT_TTL)
+    }
+}
+
+impl Expiry<String, CacheEntry> for StaleGracePeriod {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        entry: &CacheEntry,
+        _created_at: Instant,
+    ) -> Option<Duration> {
+        Some(Self::grace(entry))
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        entry: &CacheEntry,
+        _updated_at: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(Self::grace(entry))
+    }
+}
+
+pub type MetaCache = Cache<String, CacheEntry>;
+
+pub fn build_cache() -> MetaCache {
+    Cache::builder().expire_after(StaleGracePeriod).build()
+}
+
+/// The in-memory cache, optionally backed by a persistent on-disk tier.
+///
+/// Reads check memory first and fall back to disk on a miss, repopulating
+/// memory from whatever disk had; writes go to both tiers so a restart (or a
+/// sibling worker process sharing `CACHE_DIR`) doesn't cold-start.
+pub struct Store {
+    memory: MetaCache,
+    disk: Option<DiskCache>,
+}
+
+impl Store {
+    pub async fn get(&self, url: &str) -> Option<CacheEntry> {
+        if let Some(entry) = self.memory.get(url).await {
+            return Some(entry);
+        }
+        let entry = self.disk.as_ref()?.get(url).await?;
+        self.memory.insert(url.to_owned(), entry.clone()).await;
+        Some(entry)
+    }
+
+    pub async fn insert(&self, url: &str, entry: CacheEntry) {
+        if let Some(disk) = &self.disk {
+            disk.insert(url, &entry).await;
+        }
+        self.memory.insert(url.to_owned(), entry).await;
+    }
+}
+
+/// Builds the [`Store`] the server uses, enabling the disk tier when
+/// `CACHE_DIR` is set.
+pub fn build_store() -> Store {
+    let disk = std::env::var("CACHE_DIR").ok().map(DiskCache::new);
+    Store { memory: build_cache(), disk }
+}
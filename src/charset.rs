@@ -0,0 +1,69 @@
+//! Best-effort charset detection for a response body, so
+//! [`crate::fetch::fetch_text`] can decode the large fraction of the web that
+//! isn't UTF-8 instead of corrupting it.
+
+use encoding_rs::Encoding;
+
+/// Picks the encoding to decode `body` with, in the usual browser-sniffing
+/// order: the `Content-Type` header's `charset` parameter, a leading
+/// byte-order mark, a `<meta charset>` declaration in the first chunk of
+/// HTML, falling back to UTF-8.
+pub fn detect(content_type: Option<&str>, body: &[u8]) -> &'static Encoding {
+    if let Some(encoding) = content_type.and_then(from_content_type) {
+        return encoding;
+    }
+    if let Some((encoding, _)) = Encoding::for_bom(body) {
+        return encoding;
+    }
+    if let Some(encoding) = from_meta_tag(body) {
+        return encoding;
+    }
+    encoding_rs::UTF_8
+}
+
+fn from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    let charset = content_type.split(';').skip(1).find_map(|param| {
+        let (name, value) = param.trim().split_once('=')?;
+        name.eq_ignore_ascii_case("charset").then(|| value.trim_matches('"'))
+    })?;
+    Encoding::for_label(charset.as_bytes())
+}
+
+/// Scans the first 1KB of the body (a conforming page must declare its
+/// charset within that window) for `<meta charset="...">` or
+/// `<meta http-equiv="Content-Type" content="...; charset=...">`.
+fn from_meta_tag(body: &[u8]) -> Option<&'static Encoding> {
+    let head = String::from_utf8_lossy(&body[..body.len().min(1024)]).to_ascii_lowercase();
+    for tag in head.split("<meta").skip(1) {
+        if let Some(charset) = attr(tag, "charset") {
+            if let Some(encoding) = Encoding::for_label(charset.as_bytes()) {
+                return Some(encoding);
+            }
+        }
+        if attr(tag, "http-equiv").as_deref() == Some("content-type") {
+            let charset = attr(tag, "content")
+                .and_then(|content| content.split("charset=").nth(1).map(str::to_owned));
+            if let Some(charset) = charset {
+                let charset = charset.split(['"', '\'', ';']).next().unwrap_or(&charset);
+                if let Some(encoding) = Encoding::for_label(charset.trim().as_bytes()) {
+                    return Some(encoding);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Pulls a `name="value"` (or unquoted `name=value`) attribute out of the
+/// text following a `<meta` in a lowercased HTML snippet.
+fn attr(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!("{name}="))? + name.len() + 1;
+    let rest = tag[start..].trim_start();
+    match rest.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = rest[1..].find(quote)? + 1;
+            Some(rest[1..end].to_owned())
+        }
+        _ => rest.split(|c: char| c.is_whitespace() || c == '>').next().map(str::to_owned),
+    }
+}
@@ -0,0 +1,62 @@
+//! Content-addressed on-disk cache tier, so popular URLs survive restarts
+//! and can be shared across worker processes over a common volume.
+//!
+//! Enabled by setting `CACHE_DIR`; with it unset the service runs in-memory
+//! only, as before.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::MetaData;
+use crate::cache::CacheEntry;
+
+/// On-disk representation of a [`CacheEntry`]. `Instant` can't survive a
+/// restart, so freshness is tracked as a `SystemTime` the entry was written
+/// at plus the TTL it was computed from, and converted back to an `Instant`
+/// deadline on load.
+#[derive(Serialize, Deserialize)]
+struct StoredEntry {
+    result: Result<MetaData, String>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    ttl: Duration,
+    stored_at: SystemTime,
+}
+
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        DiskCache { dir: dir.as_ref().to_owned() }
+    }
+
+    pub async fn get(&self, url: &str) -> Option<CacheEntry> {
+        let bytes = cacache::read(&self.dir, url).await.ok()?;
+        let stored: StoredEntry = serde_json::from_slice(&bytes).ok()?;
+        let elapsed = stored.stored_at.elapsed().unwrap_or(stored.ttl);
+        let valid_until = Instant::now() + stored.ttl.saturating_sub(elapsed);
+        Some(CacheEntry {
+            result: stored.result,
+            etag: stored.etag,
+            last_modified: stored.last_modified,
+            ttl: stored.ttl,
+            valid_until,
+        })
+    }
+
+    pub async fn insert(&self, url: &str, entry: &CacheEntry) {
+        let stored = StoredEntry {
+            result: entry.result.clone(),
+            etag: entry.etag.clone(),
+            last_modified: entry.last_modified.clone(),
+            ttl: entry.ttl,
+            stored_at: SystemTime::now(),
+        };
+        let Ok(bytes) = serde_json::to_vec(&stored) else { return };
+        let _ = cacache::write(&self.dir, url, bytes).await;
+    }
+}
@@ -0,0 +1,95 @@
+//! Fetching and decoding the origin response for a `link_preview` lookup.
+
+use metascraper::MetaScraper;
+use reqwest::{StatusCode, Url};
+
+use crate::cache::{CacheControl, parse_cache_control};
+use crate::ssrf::{self, HostPolicy};
+use crate::{MetaData, charset, image_meta};
+
+pub const MAX_SIZE: usize = 1024 * 1024; // 1MB limit
+
+/// Validators and caching instructions pulled off an origin response.
+pub struct Revalidation {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    pub cache_control: CacheControl,
+}
+
+fn header_str(response: &reqwest::Response, name: &str) -> Option<String> {
+    response.headers().get(name)?.to_str().ok().map(str::to_owned)
+}
+
+/// Fetches `url`, buffering up to `MAX_SIZE` bytes of the raw body and
+/// decoding it once the whole (possibly truncated) buffer is in hand, so a
+/// multi-byte codepoint split across a chunk or the `MAX_SIZE` boundary
+/// doesn't corrupt the tail of the page.
+///
+/// When `etag`/`last_modified` are given, they're sent as `If-None-Match` /
+/// `If-Modified-Since`; a `304 Not Modified` response is reported back as
+/// `Ok(None)` so the caller can reuse its previously cached result.
+async fn fetch_text(
+    reqwest: &reqwest::Client,
+    hosts: &HostPolicy,
+    url: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> anyhow::Result<Option<(String, Revalidation)>> {
+    let mut headers = Vec::new();
+    if let Some(etag) = etag {
+        headers.push(("If-None-Match", etag));
+    }
+    if let Some(last_modified) = last_modified {
+        headers.push(("If-Modified-Since", last_modified));
+    }
+    let mut response =
+        ssrf::guarded_get(reqwest, hosts, Url::parse(url)?, &headers).await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let revalidation = Revalidation {
+        etag: header_str(&response, "etag"),
+        last_modified: header_str(&response, "last-modified"),
+        cache_control: parse_cache_control(header_str(&response, "cache-control").as_deref()),
+    };
+    let content_type = header_str(&response, "content-type");
+    let mut body = Vec::with_capacity(8192.min(MAX_SIZE));
+    while let Some(chunk) = response.chunk().await? {
+        let remaining = MAX_SIZE - body.len();
+        if remaining == 0 {
+            break;
+        }
+        let take = remaining.min(chunk.len());
+        body.extend_from_slice(&chunk[..take]);
+        if take < chunk.len() {
+            break;
+        }
+    }
+    let encoding = charset::detect(content_type.as_deref(), &body);
+    let (text, _, _) = encoding.decode(&body);
+    Ok(Some((text.into_owned(), revalidation)))
+}
+
+/// Fetches and scrapes `url`, or confirms via a conditional GET that a
+/// previously fetched result (identified by `etag`/`last_modified`) is still
+/// current. Returns `Ok(None)` on a `304 Not Modified`.
+///
+/// As a follow-up to scraping, also probes the page's `image` URL (if any)
+/// for its dimensions so clients can reserve layout space for it.
+pub async fn fetch_metadata(
+    reqwest: &reqwest::Client,
+    hosts: &HostPolicy,
+    url: &str,
+    etag: Option<&str>,
+    last_modified: Option<&str>,
+) -> anyhow::Result<Option<(MetaData, Revalidation)>> {
+    let Some((text, revalidation)) = fetch_text(reqwest, hosts, url, etag, last_modified).await?
+    else {
+        return Ok(None);
+    };
+    let mut metadata = MetaData::from(MetaScraper::parse(&text)?.metadata());
+    if let Some(image_url) = &metadata.image {
+        metadata.image_meta = image_meta::probe(reqwest, hosts, image_url).await;
+    }
+    Ok(Some((metadata, revalidation)))
+}
@@ -0,0 +1,121 @@
+//! Probes an `og:image` URL for its pixel dimensions, format, and size
+//! without downloading the whole image — just enough leading bytes to read
+//! each format's header. Lets clients reserve layout space for a preview
+//! thumbnail from a single `link_preview` call instead of CLS-inducing
+//! guesswork.
+
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::ssrf::{self, HostPolicy};
+
+/// Caps how much of the image we'll read looking for its header.
+const MAX_PROBE_SIZE: usize = 64 * 1024;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImageMeta {
+    pub width: u32,
+    pub height: u32,
+    pub mime: String,
+    pub bytes: Option<u64>,
+}
+
+/// Fetches just enough of `url` to determine its dimensions and format,
+/// returning `None` if the request fails, is blocked by the [`HostPolicy`],
+/// or no supported signature is found within [`MAX_PROBE_SIZE`].
+pub async fn probe(client: &reqwest::Client, hosts: &HostPolicy, url: &str) -> Option<ImageMeta> {
+    let mut response =
+        ssrf::guarded_get(client, hosts, Url::parse(url).ok()?, &[]).await.ok()?;
+    let bytes = response
+        .headers()
+        .get("content-length")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    let mut buf = Vec::with_capacity(4096);
+    while buf.len() < MAX_PROBE_SIZE {
+        let chunk = response.chunk().await.ok()??;
+        buf.extend_from_slice(&chunk);
+        if let Some((width, height, mime)) = dimensions(&buf) {
+            return Some(ImageMeta { width, height, mime: mime.to_owned(), bytes });
+        }
+    }
+    None
+}
+
+fn has(buf: &[u8], offset: usize, tag: &[u8]) -> bool {
+    buf.len() >= offset + tag.len() && &buf[offset..offset + tag.len()] == tag
+}
+
+fn dimensions(buf: &[u8]) -> Option<(u32, u32, &'static str)> {
+    png(buf).or_else(|| gif(buf)).or_else(|| webp(buf)).or_else(|| jpeg(buf))
+}
+
+fn png(buf: &[u8]) -> Option<(u32, u32, &'static str)> {
+    if buf.len() < 24 || !has(buf, 0, &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        return None;
+    }
+    // IHDR chunk: 4-byte length, "IHDR", then width/height as big-endian u32s.
+    let width = u32::from_be_bytes(buf[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(buf[20..24].try_into().ok()?);
+    Some((width, height, "image/png"))
+}
+
+fn gif(buf: &[u8]) -> Option<(u32, u32, &'static str)> {
+    if buf.len() < 10 || !has(buf, 0, b"GIF") {
+        return None;
+    }
+    let width = u16::from_le_bytes(buf[6..8].try_into().ok()?) as u32;
+    let height = u16::from_le_bytes(buf[8..10].try_into().ok()?) as u32;
+    Some((width, height, "image/gif"))
+}
+
+fn webp(buf: &[u8]) -> Option<(u32, u32, &'static str)> {
+    if buf.len() < 30 || !has(buf, 0, b"RIFF") || !has(buf, 8, b"WEBP") {
+        return None;
+    }
+    if has(buf, 12, b"VP8 ") {
+        // Simple lossy format: 14-bit little-endian width/height at offset 26/28.
+        let width = u16::from_le_bytes(buf[26..28].try_into().ok()?) & 0x3FFF;
+        let height = u16::from_le_bytes(buf[28..30].try_into().ok()?) & 0x3FFF;
+        return Some((width as u32, height as u32, "image/webp"));
+    }
+    if has(buf, 12, b"VP8L") {
+        let bits = u32::from_le_bytes(buf[21..25].try_into().ok()?);
+        let width = (bits & 0x3FFF) + 1;
+        let height = ((bits >> 14) & 0x3FFF) + 1;
+        return Some((width, height, "image/webp"));
+    }
+    if has(buf, 12, b"VP8X") {
+        let width = (u32::from_le_bytes([buf[24], buf[25], buf[26], 0]) & 0xFF_FFFF) + 1;
+        let height = (u32::from_le_bytes([buf[27], buf[28], buf[29], 0]) & 0xFF_FFFF) + 1;
+        return Some((width, height, "image/webp"));
+    }
+    None
+}
+
+fn jpeg(buf: &[u8]) -> Option<(u32, u32, &'static str)> {
+    if !has(buf, 0, &[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut offset = 2;
+    while offset + 9 <= buf.len() {
+        if buf[offset] != 0xFF {
+            offset += 1;
+            continue;
+        }
+        let marker = buf[offset + 1];
+        if (0xC0..=0xC3).contains(&marker) {
+            let height = u16::from_be_bytes(buf[offset + 5..offset + 7].try_into().ok()?) as u32;
+            let width = u16::from_be_bytes(buf[offset + 7..offset + 9].try_into().ok()?) as u32;
+            return Some((width, height, "image/jpeg"));
+        }
+        if marker == 0xD8 || marker == 0xD9 {
+            offset += 2;
+            continue;
+        }
+        let segment_len =
+            u16::from_be_bytes(buf[offset + 2..offset + 4].try_into().ok()?) as usize;
+        offset += 2 + segment_len;
+    }
+    None
+}
@@ -10,11 +10,21 @@ use actix_web::{
     http::header::{CacheControl, CacheDirective},
     web,
 };
-use metascraper::MetaScraper;
-use moka::future::Cache;
 use serde::{Deserialize, Serialize};
 
-const MAX_AGE: u64 = 86400; // 1 day in seconds
+mod cache;
+mod charset;
+mod disk;
+mod fetch;
+mod image_meta;
+mod rewrite;
+mod ssrf;
+
+use cache::{CacheEntry, Store};
+use fetch::fetch_metadata;
+use image_meta::ImageMeta;
+use rewrite::RewriteRules;
+use ssrf::HostPolicy;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -26,59 +36,35 @@ async fn main() -> std::io::Result<()> {
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(10)).pool_max_idle_per_host(10)
             .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/137.0.0.0 Safari/537.36")
+            // Redirects are followed manually so each hop can be checked by
+            // `ssrf::guarded_get` before we connect to it.
+            .redirect(reqwest::redirect::Policy::none())
+            // `check`'s own lookup is only a fast up-front 403; this is what
+            // actually constrains which address reqwest connects to, so a
+            // DNS answer that changes between validation and connection
+            // can't smuggle a blocked address past the guard.
+            .dns_resolver(std::sync::Arc::new(ssrf::GuardedResolver::new(HostPolicy::load())))
             .build().expect("Failed to create HTTP client");
-        let cache: Cache<String, Result<MetaData, String>> =
-            Cache::builder().time_to_live(Duration::new(MAX_AGE, 0)).build();
+        let cache = cache::build_store();
+        let rewrite_rules = RewriteRules::load();
+        let hosts = HostPolicy::load();
         App::new()
             .wrap(Cors::permissive())
             .app_data(web::Data::new(client))
             .app_data(web::Data::new(cache))
+            .app_data(web::Data::new(rewrite_rules))
+            .app_data(web::Data::new(hosts))
             .service(link_preview)
     });
     app.bind((host.as_str(), port))?.run().await
 }
 
-const MAX_SIZE: usize = 1024 * 1024; // 1MB limit
-
-async fn fetch_text(reqwest: &reqwest::Client, url: &str) -> anyhow::Result<String> {
-    let response = reqwest.get(url).send().await?;
-    let (mut text, mut total_size) = (String::with_capacity(8192.min(MAX_SIZE)), 0);
-    let mut response = response;
-    while let Some(chunk) = response.chunk().await? {
-        let chunk_len = chunk.len();
-        if total_size + chunk_len <= MAX_SIZE {
-            text.push_str(std::str::from_utf8(&chunk)?);
-            total_size += chunk_len;
-            continue;
-        }
-        let remaining = MAX_SIZE - total_size;
-        if remaining == 0 {
-            break;
-        }
-        let valid_end = std::str::from_utf8(&chunk[..remaining])
-            .map(|_| remaining)
-            .unwrap_or_else(|e| e.valid_up_to());
-        if valid_end > 0 {
-            text.push_str(std::str::from_utf8(&chunk[..valid_end])?);
-        }
-        break;
-    }
-    Ok(text)
-}
-
-async fn fetch_metadata(
-    reqwest: &reqwest::Client,
-    url: &str,
-) -> anyhow::Result<metascraper::MetaData> {
-    Ok(MetaScraper::parse(&fetch_text(reqwest, url).await?)?.metadata())
-}
-
 #[derive(Deserialize)]
 struct LinkPreviewQuery {
     url: String,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Metatag {
     pub name: String,
     pub content: String,
@@ -90,7 +76,7 @@ impl From<metascraper::Metatag> for Metatag {
     }
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct MetaData {
     pub title: Option<String>,
     pub description: Option<String>,
@@ -98,6 +84,9 @@ pub struct MetaData {
     pub language: Option<String>,
     pub rss: Option<String>,
     pub image: Option<String>,
+    /// Dimensions/format/size of `image`, filled in by a follow-up probe
+    /// after scraping (see [`fetch::fetch_metadata`]).
+    pub image_meta: Option<ImageMeta>,
     pub amp: Option<String>,
     pub author: Option<String>,
     pub date: Option<String>,
@@ -115,6 +104,7 @@ impl From<metascraper::MetaData> for MetaData {
             language: data.language,
             rss: data.rss,
             image: data.image,
+            image_meta: None,
             amp: data.amp,
             author: data.author,
             date: data.date,
@@ -126,23 +116,140 @@ impl From<metascraper::MetaData> for MetaData {
 #[get("/link_preview")]
 async fn link_preview(
     reqwest: web::Data<reqwest::Client>,
-    cache: web::Data<Cache<String, Result<MetaData, String>>>,
+    cache: web::Data<Store>,
+    rewrite_rules: web::Data<RewriteRules>,
+    hosts: web::Data<HostPolicy>,
     query: web::Query<LinkPreviewQuery>,
 ) -> impl Responder {
     let url = &query.url;
-    let result = match cache.get(url).await {
-        Some(result) => result,
-        None => {
-            let result = fetch_metadata(&reqwest, url).await;
-            let result = result.map(MetaData::from).map_err(|e| e.to_string());
-            cache.insert(url.clone(), result.clone()).await;
-            result
-        }
+    let fetch_url = rewrite_rules.apply(url);
+    let target = match reqwest::Url::parse(&fetch_url) {
+        Ok(target) => target,
+        Err(error) => return HttpResponse::BadRequest().body(error.to_string()),
+    };
+    if let Err(error) = ssrf::check(&target, &hosts).await {
+        return HttpResponse::Forbidden().body(error.to_string());
+    }
+    let cached = cache.get(url).await;
+    let (result, max_age) = match &cached {
+        Some(entry) if entry.is_fresh() => (entry.result.clone(), entry.ttl),
+        Some(entry) => revalidate(&reqwest, &cache, &hosts, url, &fetch_url, entry).await,
+        None => fetch_fresh(&reqwest, &cache, &hosts, url, &fetch_url).await,
     };
     match result {
         Ok(metadata) => HttpResponse::Ok()
-            .insert_header(CacheControl(vec![CacheDirective::MaxAge(MAX_AGE as u32)]))
+            .insert_header(CacheControl(vec![CacheDirective::MaxAge(max_age.as_secs() as u32)]))
             .json(metadata),
         Err(error) => HttpResponse::InternalServerError().body(error),
     }
 }
+
+/// Stores a freshly fetched result under `url` according to its
+/// `Cache-Control`, returning the result and the `max-age` to advertise to
+/// the client. `no-store` responses are returned without being cached.
+async fn cache_result(
+    cache: &Store,
+    url: &str,
+    result: Result<MetaData, String>,
+    revalidation: fetch::Revalidation,
+) -> (Result<MetaData, String>, Duration) {
+    match revalidation.cache_control {
+        cache::CacheControl::NoStore => (result, Duration::ZERO),
+        cache::CacheControl::Ttl(ttl) => {
+            let entry =
+                CacheEntry::new(result.clone(), revalidation.etag, revalidation.last_modified, ttl);
+            cache.insert(url, entry).await;
+            (result, ttl)
+        }
+    }
+}
+
+/// Fetches `fetch_url` (the original `url`, possibly rewritten per
+/// [`RewriteRules`]) with no conditional headers, as when there's nothing
+/// cached yet. Results are cached under the original `url` so clients are
+/// unaffected by the rewrite.
+async fn fetch_fresh(
+    reqwest: &reqwest::Client,
+    cache: &Store,
+    hosts: &HostPolicy,
+    url: &str,
+    fetch_url: &str,
+) -> (Result<MetaData, String>, Duration) {
+    match fetch_metadata(reqwest, hosts, fetch_url, None, None).await {
+        Ok(Some((metadata, revalidation))) => {
+            cache_result(cache, url, Ok(metadata), revalidation).await
+        }
+        // `fetch_text` sends no validators here, so a well-behaved origin
+        // never returns this — but `guarded_get` passes a `304` straight
+        // through rather than treating it as a broken redirect, so an
+        // untrusted origin can still reach this arm. Treat it as an
+        // upstream error rather than panicking.
+        Ok(None) => {
+            let revalidation = fetch::Revalidation {
+                etag: None,
+                last_modified: None,
+                cache_control: cache::CacheControl::Ttl(cache::DEFAULT_TTL),
+            };
+            cache_result(
+                cache,
+                url,
+                Err("origin sent an unexpected 304 Not Modified".to_owned()),
+                revalidation,
+            )
+            .await
+        }
+        Err(error) => {
+            let revalidation = fetch::Revalidation {
+                etag: None,
+                last_modified: None,
+                cache_control: cache::CacheControl::Ttl(cache::DEFAULT_TTL),
+            };
+            cache_result(cache, url, Err(error.to_string()), revalidation).await
+        }
+    }
+}
+
+/// Re-checks a stale cache entry with a conditional GET against `fetch_url`;
+/// on `304 Not Modified` the cached result is reused and its freshness
+/// window reset.
+async fn revalidate(
+    reqwest: &reqwest::Client,
+    cache: &Store,
+    hosts: &HostPolicy,
+    url: &str,
+    fetch_url: &str,
+    entry: &CacheEntry,
+) -> (Result<MetaData, String>, Duration) {
+    let fetched = fetch_metadata(
+        reqwest,
+        hosts,
+        fetch_url,
+        entry.etag.as_deref(),
+        entry.last_modified.as_deref(),
+    )
+    .await;
+    match fetched {
+        Ok(None) => {
+            let refreshed = CacheEntry::new(
+                entry.result.clone(),
+                entry.etag.clone(),
+                entry.last_modified.clone(),
+                entry.ttl,
+            );
+            let ttl = refreshed.ttl;
+            cache.insert(url, refreshed).await;
+            (entry.result.clone(), ttl)
+        }
+        Ok(Some((metadata, revalidation))) => {
+            cache_result(cache, url, Ok(metadata), revalidation).await
+        }
+        Err(error) => {
+            let revalidation = fetch::Revalidation {
+                etag: None,
+                last_modified: None,
+                cache_control: cache::CacheControl::Ttl(cache::DEFAULT_TTL),
+            };
+            cache_result(cache, url, Err(error.to_string()), revalidation).await
+        }
+    }
+}
@@ -0,0 +1,70 @@
+//! Rewrites user-supplied URLs to metadata-friendly mirrors before fetching,
+//! for sites (Twitter/X, Instagram, ...) that serve nothing useful to
+//! server-side scrapers.
+//!
+//! Rules are loaded once at startup from the JSON file named by
+//! `REWRITE_RULES_FILE`; absent the env var, no rewriting happens and every
+//! URL is fetched as given.
+
+use std::borrow::Cow;
+
+use regex::Regex;
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct RawRule {
+    pattern: String,
+    replacement: String,
+}
+
+struct Rule {
+    pattern: Regex,
+    replacement: String,
+}
+
+pub struct RewriteRules(Vec<Rule>);
+
+impl RewriteRules {
+    /// Loads the ordered list of rewrite rules from `REWRITE_RULES_FILE` (a
+    /// JSON array of `{"pattern": "...", "replacement": "..."}` objects,
+    /// where `pattern` is matched against the whole URL and `replacement`
+    /// may reference its capture groups as `$1`). Returns an empty rule set
+    /// if the env var is unset or the file can't be read/parsed.
+    pub fn load() -> Self {
+        let Ok(path) = std::env::var("REWRITE_RULES_FILE") else {
+            return RewriteRules(Vec::new());
+        };
+        let rules = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Vec<RawRule>>(&contents).ok())
+            .map(|raw| {
+                raw.into_iter()
+                    .filter_map(|rule| {
+                        Regex::new(&rule.pattern)
+                            .ok()
+                            .map(|pattern| Rule { pattern, replacement: rule.replacement })
+                    })
+                    .collect()
+            });
+        match rules {
+            Some(rules) => RewriteRules(rules),
+            None => {
+                eprintln!("warning: failed to load rewrite rules from {path}");
+                RewriteRules(Vec::new())
+            }
+        }
+    }
+
+    /// Returns the URL to actually fetch: the result of the first matching
+    /// rule, or `url` unchanged if none match. Callers should still
+    /// cache/key results under the original `url` so clients see no
+    /// difference.
+    pub fn apply<'a>(&self, url: &'a str) -> Cow<'a, str> {
+        for rule in &self.0 {
+            if rule.pattern.is_match(url) {
+                return Cow::Owned(rule.pattern.replace(url, rule.replacement.as_str()).into_owned());
+            }
+        }
+        Cow::Borrowed(url)
+    }
+}
@@ -0,0 +1,215 @@
+//! Guards against the fetcher being used to reach internal services
+//! (cloud metadata endpoints, localhost, RFC1918 ranges, ...) — a classic
+//! SSRF. [`check`] is called once before the first request for a URL and
+//! again on every redirect hop it follows (see [`guarded_get`]; the client
+//! built in `main.rs` disables reqwest's own redirect handling so each hop
+//! can be re-checked).
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{StatusCode, Url};
+
+/// Redirect hops we'll follow before giving up.
+pub const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug)]
+pub enum GuardError {
+    Scheme(String),
+    MissingHost,
+    Host(String),
+    Blocked(IpAddr),
+    Dns(String),
+}
+
+impl std::fmt::Display for GuardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GuardError::Scheme(scheme) => write!(f, "scheme {scheme:?} is not allowed"),
+            GuardError::MissingHost => write!(f, "URL has no host"),
+            GuardError::Host(host) => write!(f, "host {host:?} is not allowed"),
+            GuardError::Blocked(ip) => write!(f, "target resolves to a disallowed address: {ip}"),
+            GuardError::Dns(error) => write!(f, "could not resolve target host: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for GuardError {}
+
+/// Optional host allow/deny lists, configured via `ALLOWED_HOSTS` /
+/// `DENIED_HOSTS` (comma-separated hostnames). An empty/unset `ALLOWED_HOSTS`
+/// means every host not explicitly denied is allowed.
+#[derive(Clone)]
+pub struct HostPolicy {
+    allow: Option<Vec<String>>,
+    deny: Vec<String>,
+}
+
+impl HostPolicy {
+    pub fn load() -> Self {
+        HostPolicy {
+            allow: std::env::var("ALLOWED_HOSTS").ok().map(|value| split_list(&value)),
+            deny: std::env::var("DENIED_HOSTS").ok().map(|value| split_list(&value)).unwrap_or_default(),
+        }
+    }
+
+    fn check(&self, host: &str) -> Result<(), GuardError> {
+        if self.deny.iter().any(|denied| denied.eq_ignore_ascii_case(host)) {
+            return Err(GuardError::Host(host.to_owned()));
+        }
+        if let Some(allow) = &self.allow {
+            if !allow.iter().any(|allowed| allowed.eq_ignore_ascii_case(host)) {
+                return Err(GuardError::Host(host.to_owned()));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn split_list(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|host| !host.is_empty()).map(str::to_owned).collect()
+}
+
+/// Validates `url`'s scheme and host policy, then resolves its host and
+/// rejects it if any resolved address is loopback, link-local, private, or
+/// unique-local.
+pub async fn check(url: &Url, hosts: &HostPolicy) -> Result<(), GuardError> {
+    match url.scheme() {
+        "http" | "https" => {}
+        scheme => return Err(GuardError::Scheme(scheme.to_owned())),
+    }
+    let host = url.host_str().ok_or(GuardError::MissingHost)?;
+    hosts.check(host)?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|error| GuardError::Dns(error.to_string()))?;
+    for addr in addrs {
+        if is_blocked(addr.ip()) {
+            return Err(GuardError::Blocked(addr.ip()));
+        }
+    }
+    Ok(())
+}
+
+fn is_blocked(ip: IpAddr) -> bool {
+    // `to_canonical` turns IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) into
+    // their plain `Ipv4Addr` form so they hit the same checks as a literal
+    // IPv4 address instead of sailing past the `IpAddr::V6` arm unblocked.
+    match ip.to_canonical() {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_private()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || is_shared(ip)
+        }
+        IpAddr::V6(ip) => {
+            ip.is_loopback() || ip.is_unspecified() || is_unique_local(ip) || is_unicast_link_local(ip)
+        }
+    }
+}
+
+/// `100.64.0.0/10`, the CGNAT shared address space; not yet stable as
+/// `Ipv4Addr::is_shared`.
+fn is_shared(ip: Ipv4Addr) -> bool {
+    ip.octets()[0] == 100 && (ip.octets()[1] & 0xC0) == 0x40
+}
+
+/// `fc00::/7`; not yet stable as `Ipv6Addr::is_unique_local`.
+fn is_unique_local(ip: Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xfe00) == 0xfc00
+}
+
+/// `fe80::/10`; not yet stable as `Ipv6Addr::is_unicast_link_local`.
+fn is_unicast_link_local(ip: Ipv6Addr) -> bool {
+    (ip.segments()[0] & 0xffc0) == 0xfe80
+}
+
+/// Performs a GET against `target`, validating it and every redirect hop
+/// with [`check`] before the request for that hop is sent. Requires the
+/// client to have its own redirect handling disabled
+/// (`redirect::Policy::none()`) so this is the only thing following
+/// redirects.
+pub async fn guarded_get(
+    client: &reqwest::Client,
+    hosts: &HostPolicy,
+    mut target: Url,
+    headers: &[(&str, &str)],
+) -> anyhow::Result<reqwest::Response> {
+    for _ in 0..=MAX_REDIRECTS {
+        check(&target, hosts).await?;
+        let mut request = client.get(target.clone());
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let response = request.send().await?;
+        // `is_redirection()` is true for all of 300-399, which includes
+        // `304 Not Modified` — a body-less response with no `Location` that
+        // `fetch_text` needs to see directly, not have treated as a broken
+        // redirect. Only the codes that actually carry a `Location` are
+        // followed here.
+        let is_redirect = matches!(
+            response.status(),
+            StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT
+        );
+        if !is_redirect {
+            return Ok(response);
+        }
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| anyhow::anyhow!("redirect response missing a Location header"))?;
+        target = target.join(location)?;
+    }
+    anyhow::bail!("too many redirects")
+}
+
+/// A [`reqwest::dns::Resolve`] that hands reqwest only addresses that have
+/// already passed [`HostPolicy`] and [`is_blocked`] — so whatever reqwest
+/// dials is exactly what was validated. [`check`] validates a host by doing
+/// its own, separate `lookup_host` call up front for a fast, friendly 403;
+/// the resolver installed on the client (see `main.rs`) is what actually
+/// enforces the guard, since reusing `check`'s lookup here would leave a
+/// window where a redirecting/rebinding DNS answer differs between the
+/// validation lookup and the one reqwest itself would otherwise perform.
+pub struct GuardedResolver(HostPolicy);
+
+impl GuardedResolver {
+    pub fn new(hosts: HostPolicy) -> Self {
+        GuardedResolver(hosts)
+    }
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_owned();
+        // `Resolving` futures must be `'static`, but `&self` is only valid
+        // for this call, so the (cheaply cloneable) policy is cloned into
+        // the future rather than borrowed.
+        let policy = self.0.clone();
+        Box::pin(async move {
+            policy.check(&host).map_err(box_dns_error)?;
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .map_err(box_dns_error)?
+                .filter(|addr| !is_blocked(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(box_dns_error(GuardError::Blocked(IpAddr::V4(Ipv4Addr::UNSPECIFIED))));
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn box_dns_error(error: impl std::error::Error + Send + Sync + 'static) -> Box<dyn std::error::Error + Send + Sync> {
+    Box::new(error)
+}